@@ -7,6 +7,8 @@ extern crate alloc;
 mod key;
 mod slab;
 
+pub use allocator_api2::alloc::{Allocator, Global};
+pub use allocator_api2::collections::TryReserveError;
 pub use slab::Slab;
 
 /// A [`Slab`] which can hold up to 255 elements.
@@ -1,10 +1,16 @@
-use alloc::boxed::Box;
 use core::{
     fmt,
     mem::{self, ManuallyDrop},
     ptr,
 };
 
+use allocator_api2::{
+    alloc::{Allocator, Global},
+    boxed::Box,
+    collections::TryReserveError,
+    vec::Vec,
+};
+
 use crate::key::Key;
 
 union Slot<T, K: Key> {
@@ -51,36 +57,55 @@ union Slot<T, K: Key> {
 ///     assert_eq!(slab.len(), 3);
 /// }
 /// ```
-pub struct Slab<T, K: Key> {
-    slots: Box<[Slot<T, K>]>,
+pub struct Slab<T, K: Key, A: Allocator = Global> {
+    slots: Box<[Slot<T, K>], A>,
     next: K,
     len: K,
 }
 
 impl<T, K: Key> Slab<T, K> {
-    /// Create a new [`Slab`]. No allocations will occur until the first [`insert`](Slab::insert).
+    /// Create a new [`Slab`] backed by the [`Global`] allocator. No allocations will occur until
+    /// the first [`insert`](Slab::insert).
     #[inline]
     #[must_use]
     pub fn new() -> Self {
+        Self::new_in(Global)
+    }
+}
+
+impl<T, K: Key, A: Allocator> Slab<T, K, A> {
+    /// Create a new [`Slab`] backed by the given allocator. No allocations will occur until the
+    /// first [`insert`](Slab::insert).
+    #[inline]
+    #[must_use]
+    pub fn new_in(alloc: A) -> Self {
         Self {
-            slots: Box::new([]),
+            slots: Vec::new_in(alloc).into_boxed_slice(),
             next: K::ZERO,
             len: K::ZERO,
         }
     }
 
+    /// Grow the backing buffer, leaving `next`/`len` untouched.
+    ///
+    /// On failure, a valid (unchanged) [`Box`] is written back through `ptr` before returning,
+    /// so the [`Slab`] remains in a sound, droppable state.
     #[inline(never)]
-    fn extend(&mut self) {
+    fn try_extend(&mut self) -> Result<(), TryReserveError> {
         const INITIAL_SIZE: usize = 4;
         let ptr: *mut _ = &mut self.slots;
         unsafe {
             let b = ptr::read(ptr);
-            let extend_by = if b.len() == 0 { INITIAL_SIZE } else { b.len() };
+            let extend_by = if b.is_empty() { INITIAL_SIZE } else { b.len() };
             let mut vec = b.into_vec();
-            vec.reserve_exact(extend_by);
+            if let Err(e) = vec.try_reserve_exact(extend_by) {
+                ptr::write(ptr, vec.into_boxed_slice());
+                return Err(e);
+            }
             vec.set_len(vec.capacity());
             ptr::write(ptr, vec.into_boxed_slice());
         }
+        Ok(())
     }
 
     /// # Safety
@@ -89,10 +114,22 @@ impl<T, K: Key> Slab<T, K> {
     /// true if the maximum value of `K` is greater or equal to that of [`usize`].
     #[inline]
     pub unsafe fn insert(&mut self, val: T) -> K {
+        unsafe { self.try_insert(val) }.unwrap()
+    }
+
+    /// Fallible version of [`insert`](Slab::insert) that returns allocation failure as an error
+    /// instead of panicking, for contexts where allocation errors must be recoverable.
+    ///
+    /// # Safety
+    ///
+    /// The number of occupied slots must be lower than the maximum value of `K`. This is trivially
+    /// true if the maximum value of `K` is greater or equal to that of [`usize`].
+    #[inline]
+    pub unsafe fn try_insert(&mut self, val: T) -> Result<K, TryReserveError> {
         let next = self.next;
 
         if next.as_usize() == self.slots.len() {
-            self.extend();
+            self.try_extend()?;
         }
 
         let slot = unsafe { self.slots.get_unchecked_mut(next.as_usize()) };
@@ -106,7 +143,7 @@ impl<T, K: Key> Slab<T, K> {
 
         slot.val = ManuallyDrop::new(val);
 
-        next
+        Ok(next)
     }
 
     /// Remove a previously inserted element from the [`Slab`]. Returns the contained `T`.
@@ -163,13 +200,12 @@ impl<T, K: Key> Slab<T, K> {
 
 impl<T, K: Key> Default for Slab<T, K> {
     #[inline]
-    #[must_use]
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<T, K: Key> fmt::Debug for Slab<T, K> {
+impl<T, K: Key, A: Allocator> fmt::Debug for Slab<T, K, A> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Slab")
             .field("next", &self.next)
@@ -180,6 +216,10 @@ impl<T, K: Key> fmt::Debug for Slab<T, K> {
 
 #[cfg(test)]
 mod tests {
+    use core::{alloc::Layout, ptr::NonNull};
+
+    use allocator_api2::alloc::{AllocError, Allocator, Global};
+
     #[test]
     fn does_not_forget_list() {
         let mut slab = crate::Slab32::new();
@@ -191,4 +231,63 @@ mod tests {
             assert_ne!(slab.insert(15), d);
         }
     }
+
+    /// An [`Allocator`] that always fails, for exercising the `try_extend` error path.
+    struct FailingAllocator;
+
+    unsafe impl Allocator for FailingAllocator {
+        fn allocate(&self, _layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            Err(AllocError)
+        }
+
+        unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {
+            // `Box`'s empty/dangling backing slice is still "deallocated" on drop even though
+            // `allocate` never succeeds; nothing was ever really allocated, so do nothing.
+        }
+    }
+
+    #[test]
+    fn try_insert_reports_alloc_failure_without_corrupting_slab() {
+        let mut slab = crate::Slab::<i32, u32, _>::new_in(FailingAllocator);
+        unsafe {
+            assert!(slab.try_insert(1).is_err());
+            assert_eq!(slab.next(), 0);
+            assert_eq!(slab.len(), 0);
+
+            // The slab must still be safely usable (and droppable) after the failed attempt.
+            assert!(slab.try_insert(2).is_err());
+            assert_eq!(slab.next(), 0);
+            assert_eq!(slab.len(), 0);
+        }
+    }
+
+    /// An [`Allocator`] distinct from [`Global`], forwarding to it, to prove `Slab` is usable
+    /// with a non-default allocator rather than only ever running through `Global`.
+    struct CustomAllocator;
+
+    unsafe impl Allocator for CustomAllocator {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            unsafe { Global.deallocate(ptr, layout) }
+        }
+    }
+
+    #[test]
+    fn new_in_works_with_a_custom_allocator() {
+        let mut slab = crate::Slab::<i32, u32, _>::new_in(CustomAllocator);
+        unsafe {
+            let a = slab.insert(1);
+            let b = slab.insert(2);
+
+            assert_eq!(*slab.get(a), 1);
+            assert_eq!(*slab.get(b), 2);
+
+            assert_eq!(slab.remove(a), 1);
+            assert_eq!(slab.len(), 1);
+            assert_eq!(slab.insert(3), a);
+        }
+    }
 }